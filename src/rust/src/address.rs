@@ -0,0 +1,80 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A parsed proxy target address, shared by every protocol in [`crate::proto`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Address {
+    V4([u8; 4]),
+    V6([u8; 16]),
+    Domain(String),
+}
+
+impl Address {
+    /// The VLESS/VMess wire address-type byte for this variant.
+    pub fn host_type(&self) -> u8 {
+        match self {
+            Address::V4(_) => 1,
+            Address::Domain(_) => 2,
+            Address::V6(_) => 3,
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::V4(bytes) => {
+                write!(f, "{}.{}.{}.{}", bytes[0], bytes[1], bytes[2], bytes[3])
+            }
+            Address::V6(bytes) => write!(f, "{}", format_ipv6_compressed(bytes)),
+            Address::Domain(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> String {
+        address.to_string()
+    }
+}
+
+/// Formats 16 raw IPv6 bytes with RFC 5952 zero-run compression (`::`).
+fn format_ipv6_compressed(bytes: &[u8; 16]) -> String {
+    let groups: Vec<u16> = bytes
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect();
+
+    // Find the longest run of two-or-more consecutive zero groups.
+    let mut best_start = None;
+    let mut best_len = 0;
+    let mut run_start = None;
+    for (i, &g) in groups.iter().enumerate() {
+        if g == 0 {
+            if run_start.is_none() {
+                run_start = Some(i);
+            }
+            let len = i + 1 - run_start.unwrap();
+            if len > best_len {
+                best_len = len;
+                best_start = run_start;
+            }
+        } else {
+            run_start = None;
+        }
+    }
+    if best_len < 2 {
+        best_start = None;
+    }
+
+    match best_start {
+        Some(start) => {
+            let end = start + best_len;
+            let head: Vec<String> = groups[..start].iter().map(|g| format!("{:x}", g)).collect();
+            let tail: Vec<String> = groups[end..].iter().map(|g| format!("{:x}", g)).collect();
+            format!("{}::{}", head.join(":"), tail.join(":"))
+        }
+        None => groups.iter().map(|g| format!("{:x}", g)).collect::<Vec<_>>().join(":"),
+    }
+}
@@ -1,32 +1,69 @@
 use wasm_bindgen::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize)]
+mod address;
+mod proto;
+mod share;
+
+pub use address::Address;
+pub use proto::{ProtocolError, ProxyProtocol};
+pub use share::vless_header_to_uri;
+
+const VLESS_VERSION: u8 = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct VlessHeader {
     pub uuid: String,
     pub command: u8,
-    pub address_type: u8,
-    pub address: String,
+    pub address: Address,
     pub port: u16,
 }
 
-fn parse_ipv4(bytes: &[u8]) -> String {
-    bytes.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(".")
+fn uuid_to_bytes(uuid: &str) -> Result<[u8; 16], JsValue> {
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 {
+        return Err(JsValue::from_str("invalid uuid"));
+    }
+    let mut out = [0u8; 16];
+    for i in 0..16 {
+        out[i] = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| JsValue::from_str("invalid uuid hex"))?;
+    }
+    Ok(out)
 }
 
-fn parse_ipv6(bytes: &[u8]) -> String {
-    let parts: Vec<String> = bytes.chunks(2).map(|chunk| {
-        let hi = chunk.get(0).copied().unwrap_or(0) as u16;
-        let lo = chunk.get(1).copied().unwrap_or(0) as u16;
-        format!("{:x}", (hi << 8) | lo)
-    }).collect();
-    parts.join(":")
+impl VlessHeader {
+    /// Serializes this header back into the wire format `parse_vless_header` reads.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        let mut out = Vec::with_capacity(24);
+        out.push(VLESS_VERSION);
+        out.extend_from_slice(&uuid_to_bytes(&self.uuid)?);
+        out.push(0); // option length, always zero on encode
+        out.push(self.command);
+        out.extend_from_slice(&self.port.to_be_bytes());
+        out.push(self.address.host_type());
+        match &self.address {
+            Address::V4(bytes) => out.extend_from_slice(bytes),
+            Address::Domain(domain) => {
+                let domain = domain.as_bytes();
+                if domain.len() > u8::MAX as usize {
+                    return Err(JsValue::from_str("domain too long"));
+                }
+                out.push(domain.len() as u8);
+                out.extend_from_slice(domain);
+            }
+            Address::V6(bytes) => out.extend_from_slice(bytes),
+        }
+        Ok(out)
+    }
 }
 
-#[wasm_bindgen]
-pub fn parse_vless_header(buf: &[u8]) -> Result<JsValue, JsValue> {
+/// Parses everything up to (and including) the address-type byte: uuid, command,
+/// port, and the index of the address-type byte. Shared by the owned and borrowing
+/// address parsers below so they only differ in how they handle the domain case.
+fn parse_vless_prefix(buf: &[u8]) -> Result<(String, u8, u16, u8, usize), ProtocolError> {
     if buf.len() < 24 {
-        return Err(JsValue::from_str("buffer too small"));
+        return Err(ProtocolError::TooShort);
     }
 
     // version at 0
@@ -46,76 +83,214 @@ pub fn parse_vless_header(buf: &[u8]) -> Result<JsValue, JsValue> {
 
     let payload_start = 17usize;
     if buf.len() <= payload_start {
-        return Err(JsValue::from_str("invalid payload"));
+        return Err(ProtocolError::TooShort);
     }
 
     let opt_len = buf[payload_start] as usize;
-    let command_index = payload_start + 1 + opt_len;
+    let command_index = payload_start
+        .checked_add(1)
+        .and_then(|v| v.checked_add(opt_len))
+        .ok_or(ProtocolError::LengthOverflow)?;
     if buf.len() <= command_index {
-        return Err(JsValue::from_str("invalid command index"));
+        return Err(ProtocolError::TooShort);
     }
 
     let command = buf[command_index];
     if command != 1 && command != 2 {
-        return Err(JsValue::from_str("unsupported command"));
+        return Err(ProtocolError::BadCommand(command));
     }
 
-    let port_index = command_index + 1;
-    if buf.len() < port_index + 2 {
-        return Err(JsValue::from_str("missing port"));
+    let port_index = command_index.checked_add(1).ok_or(ProtocolError::LengthOverflow)?;
+    let port_end = port_index.checked_add(2).ok_or(ProtocolError::LengthOverflow)?;
+    if buf.len() < port_end {
+        return Err(ProtocolError::TooShort);
     }
     let port = u16::from_be_bytes([buf[port_index], buf[port_index + 1]]);
 
-    let address_type_index = port_index + 2;
+    let address_type_index = port_index.checked_add(2).ok_or(ProtocolError::LengthOverflow)?;
     if buf.len() <= address_type_index {
-        return Err(JsValue::from_str("missing address type"));
+        return Err(ProtocolError::TooShort);
     }
     let address_type = buf[address_type_index];
 
-    let mut address = String::new();
-    let mut address_len = 0usize;
-    let address_value_index: usize;
+    Ok((uuid_str, command, port, address_type, address_type_index))
+}
 
+/// Computes the `[start, end)` byte range of the address value for `address_type`
+/// starting just after `address_type_index`, guarding every offset against both
+/// truncation and `usize` overflow from a crafted length byte.
+fn vless_address_range(buf: &[u8], address_type: u8, address_type_index: usize) -> Result<(usize, usize), ProtocolError> {
+    let start = address_type_index.checked_add(1).ok_or(ProtocolError::LengthOverflow)?;
     match address_type {
         1 => {
-            // IPv4: 4 bytes
-            address_value_index = address_type_index + 1;
-            if buf.len() < address_value_index + 4 { return Err(JsValue::from_str("ipv4 missing bytes")); }
-            address = parse_ipv4(&buf[address_value_index..address_value_index+4]);
-            address_len = 4;
+            let end = start.checked_add(4).ok_or(ProtocolError::LengthOverflow)?;
+            if buf.len() < end { return Err(ProtocolError::TooShort); }
+            Ok((start, end))
         }
         2 => {
-            // domain: length-prefixed
-            if buf.len() < address_type_index + 2 { return Err(JsValue::from_str("domain length missing")); }
-            let domain_len = buf[address_type_index + 1] as usize;
-            address_value_index = address_type_index + 2;
-            if buf.len() < address_value_index + domain_len { return Err(JsValue::from_str("domain bytes missing")); }
-            match std::str::from_utf8(&buf[address_value_index..address_value_index+domain_len]) {
-                Ok(s) => address = s.to_string(),
-                Err(_) => return Err(JsValue::from_str("domain utf8 error")),
-            }
-            address_len = 1 + 1 + domain_len - 1; // approximate
+            let domain_start = start.checked_add(1).ok_or(ProtocolError::LengthOverflow)?;
+            if buf.len() < domain_start { return Err(ProtocolError::TooShort); }
+            let domain_len = buf[start] as usize;
+            let domain_end = domain_start.checked_add(domain_len).ok_or(ProtocolError::LengthOverflow)?;
+            if buf.len() < domain_end { return Err(ProtocolError::TooShort); }
+            Ok((domain_start, domain_end))
         }
         3 => {
-            // ipv6: 16 bytes
-            address_value_index = address_type_index + 1;
-            if buf.len() < address_value_index + 16 { return Err(JsValue::from_str("ipv6 missing bytes")); }
-            address = parse_ipv6(&buf[address_value_index..address_value_index+16]);
-            address_len = 16;
+            let end = start.checked_add(16).ok_or(ProtocolError::LengthOverflow)?;
+            if buf.len() < end { return Err(ProtocolError::TooShort); }
+            Ok((start, end))
         }
-        _ => return Err(JsValue::from_str("invalid address type")),
+        _ => Err(ProtocolError::BadAddressType(address_type)),
     }
+}
+
+/// Core VLESS request header parser, shared by `parse_vless_header_json` and
+/// by [`proto::VlessProtocol`].
+pub(crate) fn parse_vless_bytes(buf: &[u8]) -> Result<VlessHeader, ProtocolError> {
+    let (uuid_str, command, port, address_type, address_type_index) = parse_vless_prefix(buf)?;
+    let (start, end) = vless_address_range(buf, address_type, address_type_index)?;
 
-    let hdr = VlessHeader {
+    let address = match address_type {
+        1 => {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&buf[start..end]);
+            Address::V4(bytes)
+        }
+        2 => {
+            let domain = std::str::from_utf8(&buf[start..end]).map_err(|_| ProtocolError::DomainUtf8)?;
+            Address::Domain(domain.to_string())
+        }
+        3 => {
+            let mut bytes = [0u8; 16];
+            bytes.copy_from_slice(&buf[start..end]);
+            Address::V6(bytes)
+        }
+        _ => unreachable!("vless_address_range already rejected this address_type"),
+    };
+
+    Ok(VlessHeader {
         uuid: uuid_str,
         command,
-        address_type,
         address,
         port,
+    })
+}
+
+/// Address variant that borrows the domain name straight out of the input buffer
+/// instead of allocating, used by the zero-copy `parse_vless_header` hot path.
+#[derive(Serialize)]
+enum AddressRef<'a> {
+    V4([u8; 4]),
+    V6([u8; 16]),
+    Domain(&'a str),
+}
+
+#[derive(Serialize)]
+struct VlessHeaderRef<'a> {
+    uuid: String,
+    command: u8,
+    address: AddressRef<'a>,
+    port: u16,
+}
+
+fn parse_vless_ref(buf: &[u8]) -> Result<VlessHeaderRef<'_>, ProtocolError> {
+    let (uuid_str, command, port, address_type, address_type_index) = parse_vless_prefix(buf)?;
+    let (start, end) = vless_address_range(buf, address_type, address_type_index)?;
+
+    let address = match address_type {
+        1 => {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&buf[start..end]);
+            AddressRef::V4(bytes)
+        }
+        2 => {
+            let domain = std::str::from_utf8(&buf[start..end]).map_err(|_| ProtocolError::DomainUtf8)?;
+            AddressRef::Domain(domain)
+        }
+        3 => {
+            let mut bytes = [0u8; 16];
+            bytes.copy_from_slice(&buf[start..end]);
+            AddressRef::V6(bytes)
+        }
+        _ => unreachable!("vless_address_range already rejected this address_type"),
     };
 
+    Ok(VlessHeaderRef { uuid: uuid_str, command, address, port })
+}
+
+/// Parses a VLESS request header into a structured JS object, borrowing the
+/// domain (if any) straight out of `buf` rather than allocating and re-parsing
+/// through an intermediate JSON string.
+#[wasm_bindgen]
+pub fn parse_vless_header(buf: &[u8]) -> Result<JsValue, JsValue> {
+    let hdr = parse_vless_ref(buf).map_err(|e| e.to_js())?;
+    serde_wasm_bindgen::to_value(&hdr).map_err(|e| JsValue::from_str(&format!("serialize error: {}", e)))
+}
+
+/// Back-compat shim: same parse as `parse_vless_header`, but returns the old
+/// JSON-string encoding for callers that haven't migrated yet.
+#[wasm_bindgen]
+pub fn parse_vless_header_json(buf: &[u8]) -> Result<JsValue, JsValue> {
+    let hdr = parse_vless_bytes(buf).map_err(|e| e.to_js())?;
+
     match serde_json::to_string(&hdr) {
         Ok(json_str) => Ok(JsValue::from_str(&json_str)),
         Err(e) => Err(JsValue::from_str(&format!("serialize error: {}", e))),
     }
 }
+
+/// Inverse of `parse_vless_header`: takes the object produced by it (or an equivalent
+/// `VlessHeader`-shaped object) and reconstructs the original wire frame.
+#[wasm_bindgen]
+pub fn encode_vless_header(hdr: &JsValue) -> Result<Vec<u8>, JsValue> {
+    let hdr: VlessHeader = serde_wasm_bindgen::from_value(hdr.clone())
+        .map_err(|e| JsValue::from_str(&format!("deserialize error: {}", e)))?;
+    hdr.to_bytes()
+}
+
+/// Builds the 2-byte VLESS response header (version + zero addon length) a Worker
+/// sends back after accepting a request.
+#[wasm_bindgen]
+pub fn vless_response_header() -> Vec<u8> {
+    vec![VLESS_VERSION, 0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(address: Address) -> VlessHeader {
+        VlessHeader {
+            uuid: "01020304-0506-0708-090a-0b0c0d0e0f10".to_string(),
+            command: 1,
+            address,
+            port: 443,
+        }
+    }
+
+    #[test]
+    fn round_trips_ipv4() {
+        let hdr = header(Address::V4([127, 0, 0, 1]));
+        let bytes = hdr.to_bytes().unwrap();
+        assert_eq!(parse_vless_bytes(&bytes).unwrap(), hdr);
+    }
+
+    #[test]
+    fn round_trips_domain() {
+        let hdr = header(Address::Domain("example.com".to_string()));
+        let bytes = hdr.to_bytes().unwrap();
+        assert_eq!(parse_vless_bytes(&bytes).unwrap(), hdr);
+    }
+
+    #[test]
+    fn round_trips_ipv6() {
+        let hdr = header(Address::V6([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]));
+        let bytes = hdr.to_bytes().unwrap();
+        assert_eq!(parse_vless_bytes(&bytes).unwrap(), hdr);
+    }
+
+    #[test]
+    fn response_header_is_version_and_zero_addon_len() {
+        assert_eq!(vless_response_header(), vec![VLESS_VERSION, 0]);
+    }
+}
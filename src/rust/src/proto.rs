@@ -0,0 +1,529 @@
+use std::fmt;
+
+use sha2::{Digest, Sha224};
+use wasm_bindgen::prelude::*;
+
+use crate::address::Address;
+use crate::{parse_vless_bytes, VlessHeader};
+
+/// Error type shared by every [`ProxyProtocol`] implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    TooShort,
+    BadCommand(u8),
+    BadAddressType(u8),
+    DomainUtf8,
+    InvalidHash,
+    LengthOverflow,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::TooShort => write!(f, "buffer too short"),
+            ProtocolError::BadCommand(c) => write!(f, "unsupported command: {}", c),
+            ProtocolError::BadAddressType(a) => write!(f, "invalid address type: {}", a),
+            ProtocolError::DomainUtf8 => write!(f, "domain is not valid utf8"),
+            ProtocolError::InvalidHash => write!(f, "password hash is not valid hex"),
+            ProtocolError::LengthOverflow => write!(f, "length field overflows the frame"),
+        }
+    }
+}
+
+impl ProtocolError {
+    /// Stable machine-readable identifier for this error, independent of the
+    /// human-readable `Display` message.
+    fn code(&self) -> &'static str {
+        match self {
+            ProtocolError::TooShort => "too_short",
+            ProtocolError::BadCommand(_) => "bad_command",
+            ProtocolError::BadAddressType(_) => "bad_address_type",
+            ProtocolError::DomainUtf8 => "domain_utf8",
+            ProtocolError::InvalidHash => "invalid_hash",
+            ProtocolError::LengthOverflow => "length_overflow",
+        }
+    }
+
+    /// Converts this error into a `{ code, message }` JS object so callers can
+    /// branch on `code` instead of string-matching `message`.
+    pub fn to_js(&self) -> JsValue {
+        #[derive(serde::Serialize)]
+        struct ErrorPayload<'a> {
+            code: &'a str,
+            message: String,
+        }
+        let payload = ErrorPayload { code: self.code(), message: self.to_string() };
+        serde_wasm_bindgen::to_value(&payload).unwrap_or_else(|_| JsValue::from_str(&self.to_string()))
+    }
+}
+
+/// Reads a SOCKS5-style (command, address-type, address, port) tail shared by
+/// Trojan's request line, starting at `offset`. ATYP follows the real SOCKS5
+/// scheme (`1`=IPv4, `3`=domain, `4`=IPv6) — the same one `ShadowsocksProtocol`
+/// uses, and distinct from VLESS/VMess's own `1`/`2`/`3` framing. Returns the
+/// parsed (command, address, port).
+fn parse_socks_tail(buf: &[u8], offset: usize) -> Result<(u8, Address, u16), ProtocolError> {
+    if buf.len() <= offset + 1 {
+        return Err(ProtocolError::TooShort);
+    }
+    let command = buf[offset];
+    let address_type = buf[offset + 1];
+    let address_start = offset.checked_add(2).ok_or(ProtocolError::LengthOverflow)?;
+
+    let (address, address_end) = match address_type {
+        1 => {
+            let end = address_start.checked_add(4).ok_or(ProtocolError::LengthOverflow)?;
+            if buf.len() < end {
+                return Err(ProtocolError::TooShort);
+            }
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&buf[address_start..end]);
+            (Address::V4(bytes), end)
+        }
+        3 => {
+            let domain_start = address_start.checked_add(1).ok_or(ProtocolError::LengthOverflow)?;
+            if buf.len() < domain_start {
+                return Err(ProtocolError::TooShort);
+            }
+            let domain_len = buf[address_start] as usize;
+            let domain_end = domain_start.checked_add(domain_len).ok_or(ProtocolError::LengthOverflow)?;
+            if buf.len() < domain_end {
+                return Err(ProtocolError::TooShort);
+            }
+            let domain = std::str::from_utf8(&buf[domain_start..domain_end])
+                .map_err(|_| ProtocolError::DomainUtf8)?;
+            (Address::Domain(domain.to_string()), domain_end)
+        }
+        4 => {
+            let end = address_start.checked_add(16).ok_or(ProtocolError::LengthOverflow)?;
+            if buf.len() < end {
+                return Err(ProtocolError::TooShort);
+            }
+            let mut bytes = [0u8; 16];
+            bytes.copy_from_slice(&buf[address_start..end]);
+            (Address::V6(bytes), end)
+        }
+        _ => return Err(ProtocolError::BadAddressType(address_type)),
+    };
+
+    let port_end = address_end.checked_add(2).ok_or(ProtocolError::LengthOverflow)?;
+    if buf.len() < port_end {
+        return Err(ProtocolError::TooShort);
+    }
+    let port = u16::from_be_bytes([buf[address_end], buf[address_end + 1]]);
+
+    Ok((command, address, port))
+}
+
+/// Common interface over the inbound request header of a proxy protocol, so a
+/// single dispatcher can decode any of them into the same (address, port) shape.
+pub trait ProxyProtocol {
+    type Header;
+
+    fn parse(buf: &[u8]) -> Result<Self::Header, ProtocolError>;
+    fn command_target(h: &Self::Header) -> (Address, u16);
+}
+
+pub struct VlessProtocol;
+
+impl ProxyProtocol for VlessProtocol {
+    type Header = VlessHeader;
+
+    fn parse(buf: &[u8]) -> Result<Self::Header, ProtocolError> {
+        parse_vless_bytes(buf)
+    }
+
+    fn command_target(h: &Self::Header) -> (Address, u16) {
+        (h.address.clone(), h.port)
+    }
+}
+
+/// Trojan request header: a hex-encoded SHA-224 password hash, a CRLF, then a
+/// SOCKS5-style (command, address, port) request and a trailing CRLF.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TrojanHeader {
+    pub password_hash: String,
+    pub command: u8,
+    pub address: Address,
+    pub port: u16,
+}
+
+pub struct TrojanProtocol;
+
+const TROJAN_HASH_HEX_LEN: usize = 56; // SHA-224 digest, hex-encoded
+
+impl TrojanProtocol {
+    /// Hashes `password` the way a Trojan client does when building a request.
+    pub fn hash_password(password: &str) -> String {
+        let digest = Sha224::digest(password.as_bytes());
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl ProxyProtocol for TrojanProtocol {
+    type Header = TrojanHeader;
+
+    fn parse(buf: &[u8]) -> Result<Self::Header, ProtocolError> {
+        if buf.len() < TROJAN_HASH_HEX_LEN + 2 {
+            return Err(ProtocolError::TooShort);
+        }
+        let hash_bytes = &buf[0..TROJAN_HASH_HEX_LEN];
+        if !hash_bytes.iter().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ProtocolError::InvalidHash);
+        }
+        let password_hash = std::str::from_utf8(hash_bytes).unwrap().to_string();
+
+        if &buf[TROJAN_HASH_HEX_LEN..TROJAN_HASH_HEX_LEN + 2] != b"\r\n" {
+            return Err(ProtocolError::TooShort);
+        }
+
+        let (command, address, port) = parse_socks_tail(buf, TROJAN_HASH_HEX_LEN + 2)?;
+        if command != 1 && command != 3 {
+            return Err(ProtocolError::BadCommand(command));
+        }
+
+        Ok(TrojanHeader { password_hash, command, address, port })
+    }
+
+    fn command_target(h: &Self::Header) -> (Address, u16) {
+        (h.address.clone(), h.port)
+    }
+}
+
+/// Shadowsocks request header: a bare SOCKS5-style (address-type, address, port)
+/// target with no command byte and no auth data in the header itself — the key
+/// is negotiated out of band via the cipher, not carried on the wire like
+/// Trojan's password hash or VMess's auth id.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShadowsocksHeader {
+    pub address: Address,
+    pub port: u16,
+}
+
+pub struct ShadowsocksProtocol;
+
+impl ProxyProtocol for ShadowsocksProtocol {
+    type Header = ShadowsocksHeader;
+
+    fn parse(buf: &[u8]) -> Result<Self::Header, ProtocolError> {
+        if buf.is_empty() {
+            return Err(ProtocolError::TooShort);
+        }
+        let address_type = buf[0];
+        let address_start = 1usize;
+
+        let (address, address_end) = match address_type {
+            1 => {
+                let end = address_start.checked_add(4).ok_or(ProtocolError::LengthOverflow)?;
+                if buf.len() < end {
+                    return Err(ProtocolError::TooShort);
+                }
+                let mut bytes = [0u8; 4];
+                bytes.copy_from_slice(&buf[address_start..end]);
+                (Address::V4(bytes), end)
+            }
+            3 => {
+                let domain_start = address_start.checked_add(1).ok_or(ProtocolError::LengthOverflow)?;
+                if buf.len() < domain_start {
+                    return Err(ProtocolError::TooShort);
+                }
+                let domain_len = buf[address_start] as usize;
+                let domain_end = domain_start.checked_add(domain_len).ok_or(ProtocolError::LengthOverflow)?;
+                if buf.len() < domain_end {
+                    return Err(ProtocolError::TooShort);
+                }
+                let domain = std::str::from_utf8(&buf[domain_start..domain_end])
+                    .map_err(|_| ProtocolError::DomainUtf8)?;
+                (Address::Domain(domain.to_string()), domain_end)
+            }
+            4 => {
+                let end = address_start.checked_add(16).ok_or(ProtocolError::LengthOverflow)?;
+                if buf.len() < end {
+                    return Err(ProtocolError::TooShort);
+                }
+                let mut bytes = [0u8; 16];
+                bytes.copy_from_slice(&buf[address_start..end]);
+                (Address::V6(bytes), end)
+            }
+            _ => return Err(ProtocolError::BadAddressType(address_type)),
+        };
+
+        let port_end = address_end.checked_add(2).ok_or(ProtocolError::LengthOverflow)?;
+        if buf.len() < port_end {
+            return Err(ProtocolError::TooShort);
+        }
+        let port = u16::from_be_bytes([buf[address_end], buf[address_end + 1]]);
+
+        Ok(ShadowsocksHeader { address, port })
+    }
+
+    fn command_target(h: &Self::Header) -> (Address, u16) {
+        (h.address.clone(), h.port)
+    }
+}
+
+/// VMess request header. This covers the legacy, unencrypted VMess framing —
+/// a 16-byte auth id followed by the same option-length/command/port/address
+/// layout VLESS uses — rather than the full AEAD handshake.
+#[derive(Debug, PartialEq, Eq)]
+pub struct VMessHeader {
+    pub auth_id: String,
+    pub command: u8,
+    pub address: Address,
+    pub port: u16,
+}
+
+pub struct VMessProtocol;
+
+impl ProxyProtocol for VMessProtocol {
+    type Header = VMessHeader;
+
+    fn parse(buf: &[u8]) -> Result<Self::Header, ProtocolError> {
+        if buf.len() < 18 {
+            return Err(ProtocolError::TooShort);
+        }
+        let auth_id = buf[0..16].iter().map(|b| format!("{:02x}", b)).collect();
+        let opt_len = buf[16] as usize;
+        let command_index = 17usize.checked_add(opt_len).ok_or(ProtocolError::LengthOverflow)?;
+        if buf.len() <= command_index {
+            return Err(ProtocolError::TooShort);
+        }
+        let command = buf[command_index];
+        if command != 1 && command != 2 {
+            return Err(ProtocolError::BadCommand(command));
+        }
+
+        let port_index = command_index.checked_add(1).ok_or(ProtocolError::LengthOverflow)?;
+        let port_end = port_index.checked_add(2).ok_or(ProtocolError::LengthOverflow)?;
+        if buf.len() < port_end {
+            return Err(ProtocolError::TooShort);
+        }
+        let port = u16::from_be_bytes([buf[port_index], buf[port_index + 1]]);
+
+        let address_type_index = port_index.checked_add(2).ok_or(ProtocolError::LengthOverflow)?;
+        if buf.len() <= address_type_index {
+            return Err(ProtocolError::TooShort);
+        }
+        let address_type = buf[address_type_index];
+        let address_start = address_type_index.checked_add(1).ok_or(ProtocolError::LengthOverflow)?;
+        let address = match address_type {
+            1 => {
+                let end = address_start.checked_add(4).ok_or(ProtocolError::LengthOverflow)?;
+                if buf.len() < end {
+                    return Err(ProtocolError::TooShort);
+                }
+                Address::V4([buf[address_start], buf[address_start + 1], buf[address_start + 2], buf[address_start + 3]])
+            }
+            2 => {
+                let domain_start = address_start.checked_add(1).ok_or(ProtocolError::LengthOverflow)?;
+                if buf.len() < domain_start {
+                    return Err(ProtocolError::TooShort);
+                }
+                let domain_len = buf[address_start] as usize;
+                let domain_end = domain_start.checked_add(domain_len).ok_or(ProtocolError::LengthOverflow)?;
+                if buf.len() < domain_end {
+                    return Err(ProtocolError::TooShort);
+                }
+                let domain = std::str::from_utf8(&buf[domain_start..domain_end])
+                    .map_err(|_| ProtocolError::DomainUtf8)?;
+                Address::Domain(domain.to_string())
+            }
+            3 => {
+                let end = address_start.checked_add(16).ok_or(ProtocolError::LengthOverflow)?;
+                if buf.len() < end {
+                    return Err(ProtocolError::TooShort);
+                }
+                let mut bytes = [0u8; 16];
+                bytes.copy_from_slice(&buf[address_start..end]);
+                Address::V6(bytes)
+            }
+            _ => return Err(ProtocolError::BadAddressType(address_type)),
+        };
+
+        Ok(VMessHeader { auth_id, command, address, port })
+    }
+
+    fn command_target(h: &Self::Header) -> (Address, u16) {
+        (h.address.clone(), h.port)
+    }
+}
+
+/// Decoded (address, port, command) target, uniform across every protocol kind.
+/// `password_hash`/`auth_id` carry each protocol's own auth data through to the
+/// caller so a Worker using this dispatcher can still verify Trojan/VMess
+/// clients instead of the data being silently dropped; they're `None` for
+/// protocols that don't carry auth data in the header (VLESS, Shadowsocks).
+#[derive(serde::Serialize)]
+struct DecodedTarget {
+    command: u8,
+    address: String,
+    port: u16,
+    password_hash: Option<String>,
+    auth_id: Option<String>,
+}
+
+/// Dispatches to the right [`ProxyProtocol`] implementation by name and returns
+/// the decoded target uniformly, regardless of which protocol framed it.
+#[wasm_bindgen]
+pub fn parse_proxy_header(kind: &str, buf: &[u8]) -> Result<JsValue, JsValue> {
+    let target = match kind {
+        "vless" => {
+            let header = VlessProtocol::parse(buf).map_err(|e| e.to_js())?;
+            let command = header.command;
+            let (address, port) = VlessProtocol::command_target(&header);
+            DecodedTarget { command, address: address.to_string(), port, password_hash: None, auth_id: None }
+        }
+        "trojan" => {
+            let header = TrojanProtocol::parse(buf).map_err(|e| e.to_js())?;
+            let command = header.command;
+            let password_hash = header.password_hash.clone();
+            let (address, port) = TrojanProtocol::command_target(&header);
+            DecodedTarget { command, address: address.to_string(), port, password_hash: Some(password_hash), auth_id: None }
+        }
+        "vmess" => {
+            let header = VMessProtocol::parse(buf).map_err(|e| e.to_js())?;
+            let command = header.command;
+            let auth_id = header.auth_id.clone();
+            let (address, port) = VMessProtocol::command_target(&header);
+            DecodedTarget { command, address: address.to_string(), port, password_hash: None, auth_id: Some(auth_id) }
+        }
+        "shadowsocks" => {
+            let header = ShadowsocksProtocol::parse(buf).map_err(|e| e.to_js())?;
+            let (address, port) = ShadowsocksProtocol::command_target(&header);
+            // Shadowsocks has no command byte on the wire; every request is a connect.
+            DecodedTarget { command: 1, address: address.to_string(), port, password_hash: None, auth_id: None }
+        }
+        other => return Err(JsValue::from_str(&format!("unknown protocol: {}", other))),
+    };
+
+    serde_wasm_bindgen::to_value(&target)
+        .map_err(|e| JsValue::from_str(&format!("serialize error: {}", e)))
+}
+
+/// Hashes `password` the way a Trojan client does, so a Worker can compare a
+/// configured secret against `DecodedTarget::password_hash` without
+/// reimplementing the SHA-224 scheme on the JS side.
+#[wasm_bindgen]
+pub fn trojan_hash_password(password: &str) -> String {
+    TrojanProtocol::hash_password(password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef0123456789abcdef0123456789abcdef01234567";
+
+    fn trojan_frame(atyp: u8, address: &[u8], port: u16) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(HASH.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+        buf.push(1); // command: connect
+        buf.push(atyp);
+        buf.extend_from_slice(address);
+        buf.extend_from_slice(&port.to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn trojan_parses_domain_target() {
+        let domain = b"example.com";
+        let mut address = vec![domain.len() as u8];
+        address.extend_from_slice(domain);
+        let buf = trojan_frame(3, &address, 443);
+
+        let hdr = TrojanProtocol::parse(&buf).unwrap();
+        assert_eq!(hdr.password_hash, HASH);
+        assert_eq!(hdr.address, Address::Domain("example.com".to_string()));
+        assert_eq!(hdr.port, 443);
+    }
+
+    #[test]
+    fn trojan_parses_ipv6_target() {
+        let address = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let buf = trojan_frame(4, &address, 443);
+
+        let hdr = TrojanProtocol::parse(&buf).unwrap();
+        assert_eq!(hdr.address, Address::V6(address));
+    }
+
+    #[test]
+    fn trojan_rejects_bad_address_type() {
+        let buf = trojan_frame(99, &[0, 0, 0, 0], 443);
+        assert_eq!(TrojanProtocol::parse(&buf), Err(ProtocolError::BadAddressType(99)));
+    }
+
+    #[test]
+    fn trojan_rejects_truncated_buffer() {
+        let buf = trojan_frame(1, &[127, 0, 0, 1], 443);
+        assert_eq!(TrojanProtocol::parse(&buf[..buf.len() - 1]), Err(ProtocolError::TooShort));
+    }
+
+    #[test]
+    fn trojan_rejects_invalid_hash() {
+        let mut buf = trojan_frame(1, &[127, 0, 0, 1], 443);
+        buf[0] = b'z'; // not a hex digit
+        assert_eq!(TrojanProtocol::parse(&buf), Err(ProtocolError::InvalidHash));
+    }
+
+    fn vmess_frame(atyp: u8, address: &[u8], port: u16) -> Vec<u8> {
+        let mut buf = vec![0u8; 16]; // auth id
+        buf.push(0); // option length
+        buf.push(1); // command: connect
+        buf.extend_from_slice(&port.to_be_bytes());
+        buf.push(atyp);
+        buf.extend_from_slice(address);
+        buf
+    }
+
+    #[test]
+    fn vmess_parses_domain_target() {
+        let domain = b"example.com";
+        let mut address = vec![domain.len() as u8];
+        address.extend_from_slice(domain);
+        let buf = vmess_frame(2, &address, 443);
+
+        let hdr = VMessProtocol::parse(&buf).unwrap();
+        assert_eq!(hdr.address, Address::Domain("example.com".to_string()));
+        assert_eq!(hdr.port, 443);
+    }
+
+    #[test]
+    fn vmess_rejects_bad_address_type() {
+        let buf = vmess_frame(99, &[0, 0, 0, 0], 443);
+        assert_eq!(VMessProtocol::parse(&buf), Err(ProtocolError::BadAddressType(99)));
+    }
+
+    #[test]
+    fn vmess_rejects_truncated_buffer() {
+        let buf = vmess_frame(1, &[127, 0, 0, 1], 443);
+        assert_eq!(VMessProtocol::parse(&buf[..buf.len() - 1]), Err(ProtocolError::TooShort));
+    }
+
+    fn shadowsocks_frame(atyp: u8, address: &[u8], port: u16) -> Vec<u8> {
+        let mut buf = vec![atyp];
+        buf.extend_from_slice(address);
+        buf.extend_from_slice(&port.to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn shadowsocks_parses_ipv4_target() {
+        let buf = shadowsocks_frame(1, &[127, 0, 0, 1], 8388);
+        let hdr = ShadowsocksProtocol::parse(&buf).unwrap();
+        assert_eq!(hdr.address, Address::V4([127, 0, 0, 1]));
+        assert_eq!(hdr.port, 8388);
+    }
+
+    #[test]
+    fn shadowsocks_rejects_bad_address_type() {
+        let buf = shadowsocks_frame(99, &[0, 0, 0, 0], 8388);
+        assert_eq!(ShadowsocksProtocol::parse(&buf), Err(ProtocolError::BadAddressType(99)));
+    }
+
+    #[test]
+    fn shadowsocks_rejects_truncated_buffer() {
+        let buf = shadowsocks_frame(1, &[127, 0, 0, 1], 8388);
+        assert_eq!(ShadowsocksProtocol::parse(&buf[..buf.len() - 1]), Err(ProtocolError::TooShort));
+    }
+}
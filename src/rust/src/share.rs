@@ -0,0 +1,102 @@
+use url::Url;
+use wasm_bindgen::prelude::*;
+
+use crate::VlessHeader;
+
+/// Converts `host` into the form that belongs in a URL's authority: IPv6
+/// literals are bracketed so the URL host parser recognizes them, and domain
+/// names are run through IDNA so Unicode labels come out as ASCII punycode.
+/// `vless` isn't a "special" scheme, so `url::Url` won't run IDNA for us —
+/// we have to do it ourselves before handing the host to `set_host`.
+fn prepare_host(host: &str) -> Result<String, String> {
+    if host.contains(':') {
+        if host.starts_with('[') && host.ends_with(']') {
+            Ok(host.to_string())
+        } else {
+            Ok(format!("[{}]", host))
+        }
+    } else {
+        idna::domain_to_ascii(host).map_err(|e| format!("invalid host: {}", e))
+    }
+}
+
+/// Builds a `vless://` subscription link for `hdr`. Pulled out of the
+/// `#[wasm_bindgen]` entry point so the logic can be unit tested without a JS
+/// runtime. Uses `url::Url`'s own setters for the userinfo/host/port so
+/// escaping and IPv6 bracketing are handled by the crate instead of by hand.
+pub(crate) fn build_vless_uri(hdr: &VlessHeader, host: &str, path: &str) -> Result<String, String> {
+    let mut url = Url::parse("vless://placeholder").map_err(|e| format!("url error: {}", e))?;
+
+    url.set_username(&hdr.uuid)
+        .map_err(|_| "invalid uuid for url".to_string())?;
+
+    let ascii_host = prepare_host(host)?;
+    url.set_host(Some(&ascii_host))
+        .map_err(|e| format!("invalid host: {}", e))?;
+
+    url.set_port(Some(hdr.port))
+        .map_err(|_| "invalid port for url".to_string())?;
+
+    url.query_pairs_mut()
+        .append_pair("type", "ws")
+        .append_pair("security", "none")
+        .append_pair("path", path);
+
+    url.set_fragment(Some(&format!("{}:{}", host, hdr.port)));
+
+    Ok(url.to_string())
+}
+
+/// Builds a `vless://` subscription link for `hdr`, so a Worker can expose its own
+/// config as a shareable/QR-able URI. `host` is the address clients should connect
+/// to (bracketed automatically if it's a bare IPv6 literal, punycode-encoded if
+/// it's a Unicode domain) and `path` is the WebSocket path the Worker listens on.
+#[wasm_bindgen]
+pub fn vless_header_to_uri(hdr: &JsValue, host: &str, path: &str) -> Result<String, JsValue> {
+    let hdr: VlessHeader = serde_wasm_bindgen::from_value(hdr.clone())
+        .map_err(|e| JsValue::from_str(&format!("deserialize error: {}", e)))?;
+    build_vless_uri(&hdr, host, path).map_err(|e| JsValue::from_str(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Address;
+
+    fn header(port: u16) -> VlessHeader {
+        VlessHeader {
+            uuid: "01020304-0506-0708-090a-0b0c0d0e0f10".to_string(),
+            command: 1,
+            address: Address::V4([10, 0, 0, 1]),
+            port,
+        }
+    }
+
+    #[test]
+    fn ipv6_host_is_bracketed() {
+        let uri = build_vless_uri(&header(443), "2001:db8::1", "/ws").unwrap();
+        assert!(uri.contains("@[2001:db8::1]:443"), "uri was: {}", uri);
+    }
+
+    #[test]
+    fn already_bracketed_ipv6_host_is_not_double_bracketed() {
+        let uri = build_vless_uri(&header(443), "[2001:db8::1]", "/ws").unwrap();
+        assert!(uri.contains("@[2001:db8::1]:443"), "uri was: {}", uri);
+    }
+
+    #[test]
+    fn unicode_domain_is_punycoded() {
+        let uri = build_vless_uri(&header(443), "münchen.example.com", "/ws").unwrap();
+        assert!(uri.contains("xn--mnchen-3ya.example.com"), "uri was: {}", uri);
+        assert!(!uri.contains('ü'), "uri was: {}", uri);
+    }
+
+    #[test]
+    fn malicious_uuid_does_not_corrupt_the_authority() {
+        let mut hdr = header(443);
+        hdr.uuid = "evil@attacker.example".to_string();
+        let uri = build_vless_uri(&hdr, "example.com", "/ws").unwrap();
+        let parsed = Url::parse(&uri).unwrap();
+        assert_eq!(parsed.host_str(), Some("example.com"));
+    }
+}